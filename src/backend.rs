@@ -0,0 +1,368 @@
+//! Pluggable audio output backends selected at startup via `--backend`.
+//!
+//! `PlaybackEngine` talks to whichever backend is open through the
+//! `AudioSink` trait rather than `rodio::Sink` directly, so `create_sink`
+//! only needs to look a name up in `BACKENDS` and construct it.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rodio::{cpal, OutputStream, Sink as RodioSink, Source};
+
+/// A lazily-decoded, type-erased audio stream: a file `Decoder` and a
+/// network `NetworkTrackSource` both implement `Source<Item = i16>`, so
+/// `AudioSink` only has to deal with one concrete type.
+pub type AudioSource = Box<dyn Source<Item = i16> + Send>;
+
+/// A single output transport. Mirrors the subset of `rodio::Sink` the
+/// playback engine relies on, so the `rodio` backend is a thin pass-through
+/// and the headless backends can fake the same surface.
+pub trait AudioSink: Send {
+    fn play(&mut self);
+    fn pause(&mut self);
+    fn append(&mut self, source: AudioSource);
+    fn stop(&mut self);
+    fn clear(&mut self);
+    /// Drops only the currently playing source, letting whatever's queued
+    /// behind it start immediately, unlike `clear` which drops everything.
+    fn skip_one(&mut self);
+    fn set_volume(&mut self, volume: f32);
+    fn try_seek(&mut self, position: Duration) -> Result<(), Box<dyn std::error::Error>>;
+    fn get_pos(&self) -> Duration;
+    fn len(&self) -> usize;
+    fn empty(&self) -> bool;
+}
+
+/// Backend names recognized by `--backend`, mapped to their constructors.
+/// `device` is `None` for every backend but `rodio`, which is the only one
+/// that talks to an actual output device.
+const BACKENDS: &[(&str, fn(Option<&cpal::Device>) -> Result<Box<dyn AudioSink>, Box<dyn std::error::Error>>)] = &[
+    ("rodio", RodioBackend::open),
+    ("pipe", PipeBackend::open),
+    ("null", NullBackend::open),
+];
+
+/// Looks `name` up in `BACKENDS` and opens it on `device` (ignored by the
+/// headless backends, required for `rodio`).
+pub fn create_sink(name: &str, device: Option<&cpal::Device>) -> Result<Box<dyn AudioSink>, Box<dyn std::error::Error>> {
+    let (_, open) = BACKENDS
+        .iter()
+        .find(|(backend_name, _)| *backend_name == name)
+        .ok_or_else(|| format!("unknown audio backend: {}", name))?;
+    open(device)
+}
+
+/// The default backend: plays through the host's audio device via rodio.
+struct RodioBackend {
+    _stream: OutputStream,
+    sink: RodioSink,
+}
+
+impl RodioBackend {
+    fn open(device: Option<&cpal::Device>) -> Result<Box<dyn AudioSink>, Box<dyn std::error::Error>> {
+        let device = device.ok_or("the rodio backend requires an output device")?;
+        let (stream, handle) = OutputStream::try_from_device(device)?;
+        let sink = RodioSink::try_new(&handle)?;
+        Ok(Box::new(Self { _stream: stream, sink }))
+    }
+}
+
+impl AudioSink for RodioBackend {
+    fn play(&mut self) {
+        self.sink.play();
+    }
+
+    fn pause(&mut self) {
+        self.sink.pause();
+    }
+
+    fn append(&mut self, source: AudioSource) {
+        self.sink.append(source);
+    }
+
+    fn stop(&mut self) {
+        self.sink.stop();
+    }
+
+    fn clear(&mut self) {
+        self.sink.clear();
+    }
+
+    fn skip_one(&mut self) {
+        self.sink.skip_one();
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        self.sink.try_seek(position).map_err(|e| e.to_string().into())
+    }
+
+    fn get_pos(&self) -> Duration {
+        self.sink.get_pos()
+    }
+
+    fn len(&self) -> usize {
+        self.sink.len()
+    }
+
+    fn empty(&self) -> bool {
+        self.sink.empty()
+    }
+}
+
+/// Tracks elapsed playback time against a wall clock for the backends that
+/// don't have a real playback device to ask for a position, shared by
+/// `pipe` and `null`. Mirrors the engine's own `current_total: Option<Duration>`
+/// convention for tracks whose length isn't known.
+struct Timeline {
+    inner: RefCell<TimelineState>,
+}
+
+struct TimelineState {
+    queued_totals: VecDeque<Option<Duration>>,
+    current_total: Option<Duration>,
+    current_elapsed: Duration,
+    clock_mark: Option<Instant>,
+}
+
+impl Timeline {
+    fn new() -> Self {
+        Self {
+            inner: RefCell::new(TimelineState {
+                queued_totals: VecDeque::new(),
+                current_total: None,
+                current_elapsed: Duration::ZERO,
+                clock_mark: None,
+            }),
+        }
+    }
+
+    fn push(&self, total: Option<Duration>) {
+        let mut state = self.inner.borrow_mut();
+        if state.current_total.is_none() && state.queued_totals.is_empty() {
+            state.current_total = total;
+            state.current_elapsed = Duration::ZERO;
+        } else {
+            state.queued_totals.push_back(total);
+        }
+    }
+
+    fn play(&self) {
+        self.inner.borrow_mut().clock_mark = Some(Instant::now());
+    }
+
+    fn pause(&self) {
+        let mut state = self.inner.borrow_mut();
+        tick(&mut state);
+        state.clock_mark = None;
+    }
+
+    fn stop(&self) {
+        let mut state = self.inner.borrow_mut();
+        state.queued_totals.clear();
+        state.current_total = None;
+        state.current_elapsed = Duration::ZERO;
+        state.clock_mark = None;
+    }
+
+    fn clear(&self) {
+        self.stop();
+    }
+
+    /// Drops only the current track's timing, moving straight to whatever's
+    /// next queued rather than clearing the whole queue.
+    fn skip_one(&self) {
+        let mut state = self.inner.borrow_mut();
+        state.current_total = state.queued_totals.pop_front().flatten();
+        state.current_elapsed = Duration::ZERO;
+    }
+
+    fn seek(&self, position: Duration) {
+        let mut state = self.inner.borrow_mut();
+        state.current_elapsed = match state.current_total {
+            Some(total) => position.min(total),
+            None => position,
+        };
+        if state.clock_mark.is_some() {
+            state.clock_mark = Some(Instant::now());
+        }
+    }
+
+    fn get_pos(&self) -> Duration {
+        let mut state = self.inner.borrow_mut();
+        tick(&mut state);
+        state.current_elapsed
+    }
+
+    fn len(&self) -> usize {
+        let mut state = self.inner.borrow_mut();
+        tick(&mut state);
+        let current = if state.current_total.is_some() { 1 } else { 0 };
+        current + state.queued_totals.len()
+    }
+
+    fn empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Advances `current_elapsed` by the time since the last tick, then rolls
+/// over to the next queued track once the current one has run its course.
+fn tick(state: &mut TimelineState) {
+    if let Some(mark) = state.clock_mark {
+        state.current_elapsed += mark.elapsed();
+        state.clock_mark = Some(Instant::now());
+    }
+
+    let finished = matches!(state.current_total, Some(total) if state.current_elapsed >= total);
+    if finished {
+        state.current_total = state.queued_totals.pop_front().flatten();
+        state.current_elapsed = Duration::ZERO;
+    }
+}
+
+/// Writes raw decoded PCM (i16 interleaved) to stdout, for piping into other
+/// tools; redirect stdout to a file to capture it instead. Produces no real
+/// audio device timing, so elapsed time is modeled the same way as `null`.
+///
+/// Decoding and writing a track is handed off to a background thread so
+/// `append` returns immediately; `PlaybackEngine` calls it from its own
+/// thread assuming it's as cheap as handing a source to a real sink.
+///
+/// `stop`, `clear` and `skip_one` only reset the timeline: every source
+/// already handed to the writer thread is still written out in full.
+struct PipeBackend {
+    sources: mpsc::Sender<AudioSource>,
+    timeline: Timeline,
+}
+
+impl PipeBackend {
+    fn open(_device: Option<&cpal::Device>) -> Result<Box<dyn AudioSink>, Box<dyn std::error::Error>> {
+        let (sources, rx) = mpsc::channel::<AudioSource>();
+        thread::spawn(move || {
+            let mut out = io::stdout();
+            for source in rx {
+                for sample in source {
+                    let _ = out.write_all(&sample.to_le_bytes());
+                }
+                let _ = out.flush();
+            }
+        });
+
+        Ok(Box::new(Self {
+            sources,
+            timeline: Timeline::new(),
+        }))
+    }
+}
+
+impl AudioSink for PipeBackend {
+    fn play(&mut self) {
+        self.timeline.play();
+    }
+
+    fn pause(&mut self) {
+        self.timeline.pause();
+    }
+
+    fn append(&mut self, source: AudioSource) {
+        self.timeline.push(source.total_duration());
+        let _ = self.sources.send(source);
+    }
+
+    fn stop(&mut self) {
+        self.timeline.stop();
+    }
+
+    fn clear(&mut self) {
+        self.timeline.clear();
+    }
+
+    fn skip_one(&mut self) {
+        self.timeline.skip_one();
+    }
+
+    fn set_volume(&mut self, _volume: f32) {}
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        self.timeline.seek(position);
+        Ok(())
+    }
+
+    fn get_pos(&self) -> Duration {
+        self.timeline.get_pos()
+    }
+
+    fn len(&self) -> usize {
+        self.timeline.len()
+    }
+
+    fn empty(&self) -> bool {
+        self.timeline.empty()
+    }
+}
+
+/// Advances timing by each source's reported length but produces no sound,
+/// for testing the queue/seek logic headlessly. Appended sources are dropped
+/// without their samples being decoded.
+struct NullBackend {
+    timeline: Timeline,
+}
+
+impl NullBackend {
+    fn open(_device: Option<&cpal::Device>) -> Result<Box<dyn AudioSink>, Box<dyn std::error::Error>> {
+        Ok(Box::new(Self { timeline: Timeline::new() }))
+    }
+}
+
+impl AudioSink for NullBackend {
+    fn play(&mut self) {
+        self.timeline.play();
+    }
+
+    fn pause(&mut self) {
+        self.timeline.pause();
+    }
+
+    fn append(&mut self, source: AudioSource) {
+        self.timeline.push(source.total_duration());
+    }
+
+    fn stop(&mut self) {
+        self.timeline.stop();
+    }
+
+    fn clear(&mut self) {
+        self.timeline.clear();
+    }
+
+    fn skip_one(&mut self) {
+        self.timeline.skip_one();
+    }
+
+    fn set_volume(&mut self, _volume: f32) {}
+
+    fn try_seek(&mut self, position: Duration) -> Result<(), Box<dyn std::error::Error>> {
+        self.timeline.seek(position);
+        Ok(())
+    }
+
+    fn get_pos(&self) -> Duration {
+        self.timeline.get_pos()
+    }
+
+    fn len(&self) -> usize {
+        self.timeline.len()
+    }
+
+    fn empty(&self) -> bool {
+        self.timeline.empty()
+    }
+}
@@ -0,0 +1,8 @@
+//! Shared playback engine and supporting modules, reused by the local
+//! file-browser binary and the network streaming client/server.
+
+pub mod backend;
+pub mod library;
+pub mod network;
+pub mod player;
+pub mod playlist;
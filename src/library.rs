@@ -0,0 +1,90 @@
+use std::path::{Path, PathBuf};
+
+use dialoguer::MultiSelect;
+use walkdir::WalkDir;
+
+use crate::player::Track;
+
+pub enum Entry {
+    File(String),
+    Album(PathBuf),
+}
+
+impl Entry {
+    pub fn label(&self) -> String {
+        match self {
+            Entry::File(path) => path.clone(),
+            Entry::Album(dir) => format!("[album] {}", dir.display()),
+        }
+    }
+}
+
+/// Lists playable MP3 files in `root`, plus any subdirectory that itself
+/// contains MP3s, so an "album" can be queued as a single unit.
+pub fn collect_entries(root: &str) -> Vec<Entry> {
+    let mut albums = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in WalkDir::new(root).min_depth(1).into_iter().filter_map(Result::ok) {
+        let path = entry.path();
+        if path.is_dir() {
+            if !album_tracks(path).is_empty() {
+                albums.push(Entry::Album(path.to_path_buf()));
+            }
+        } else if path.extension().map_or(false, |ext| ext == "mp3") {
+            files.push(Entry::File(path.display().to_string()));
+        }
+    }
+
+    albums.into_iter().chain(files).collect()
+}
+
+/// Recursively collects every MP3 under `root`, for the network server's
+/// shuffled stream.
+pub fn collect_all_mp3s(root: &str) -> Vec<String> {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().map_or(false, |ext| ext == "mp3"))
+        .map(|e| e.path().display().to_string())
+        .collect()
+}
+
+/// Returns the MP3 files directly inside `dir`, sorted for stable playback order.
+pub fn album_tracks(dir: &Path) -> Vec<Track> {
+    let mut tracks: Vec<String> = WalkDir::new(dir)
+        .max_depth(1)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().map_or(false, |ext| ext == "mp3"))
+        .map(|e| e.path().display().to_string())
+        .collect();
+    tracks.sort();
+    tracks.into_iter().map(Track::from_path).collect()
+}
+
+/// Lets the user multi-select which files go into the queue, defaulting to
+/// the single file they had highlighted in the main menu.
+pub fn multi_select_queue(entries: &[Entry], default_selection: usize) -> Result<Vec<Track>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    let mut default_file_index = None;
+
+    for (i, entry) in entries.iter().enumerate() {
+        if let Entry::File(path) = entry {
+            if i == default_selection {
+                default_file_index = Some(files.len());
+            }
+            files.push(path.clone());
+        }
+    }
+
+    let defaults: Vec<bool> = (0..files.len()).map(|i| Some(i) == default_file_index).collect();
+
+    let chosen = MultiSelect::new()
+        .with_prompt("Select tracks for the queue (space to toggle, enter to confirm)")
+        .items(&files)
+        .defaults(&defaults)
+        .interact()?;
+
+    Ok(chosen.into_iter().map(|i| Track::from_path(files[i].clone())).collect())
+}
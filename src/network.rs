@@ -0,0 +1,218 @@
+//! `--serve`/`--listen` streaming mode: a server shuffles the local library
+//! and streams decoded PCM to any connected client over small
+//! length-prefixed frames, a track's metadata preceding its audio, which is
+//! itself sent as a run of fixed-size chunks so playback can start on the
+//! first chunk rather than waiting for a whole track to cross the wire. A
+//! zero-length frame marks the end of a track.
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use rand::seq::SliceRandom;
+use rodio::{cpal, Source};
+
+use crate::backend::create_sink;
+use crate::library::collect_all_mp3s;
+use crate::player::{decode_file, play_from_source, Track, TrackSource};
+
+/// How many interleaved i16 samples go out per audio frame.
+const CHUNK_SAMPLES: usize = 16_384;
+
+/// Recursively collects the local library, shuffles it, and streams each
+/// track to every connecting client as a metadata frame followed by a run
+/// of PCM chunk frames and a zero-length frame marking the track's end.
+pub fn serve(addr: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind(addr)?;
+    println!("Serving shuffled library on {}", addr);
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        thread::spawn(move || {
+            if let Err(e) = serve_client(stream) {
+                eprintln!("client disconnected: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn serve_client(mut stream: TcpStream) -> Result<(), Box<dyn std::error::Error>> {
+    let mut tracks = collect_all_mp3s(".");
+    tracks.shuffle(&mut rand::thread_rng());
+
+    for path in tracks {
+        let decoder = match decode_file(&path) {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+        let sample_rate = decoder.sample_rate();
+        let channels = decoder.channels();
+
+        let title = Path::new(&path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.clone());
+
+        write_frame(&mut stream, &encode_header(&title, sample_rate, channels))?;
+
+        let mut chunk = Vec::with_capacity(CHUNK_SAMPLES);
+        for sample in decoder {
+            chunk.push(sample);
+            if chunk.len() == CHUNK_SAMPLES {
+                write_frame(&mut stream, &encode_samples(&chunk))?;
+                chunk.clear();
+            }
+        }
+        if !chunk.is_empty() {
+            write_frame(&mut stream, &encode_samples(&chunk))?;
+        }
+        write_frame(&mut stream, &[])?;
+    }
+
+    Ok(())
+}
+
+/// Connects to a `serve` peer and plays whatever it streams through the
+/// same `PlaybackEngine`/`AudioSink` machinery the local queue uses, so
+/// pause/volume/next and the elapsed time work the same way. A streamed
+/// track reports no total length and `NetworkTrackSource` cannot seek, so
+/// the progress bar shows no total and the seek keys do not move the audio.
+/// `device` is ignored by every backend but `rodio`.
+pub fn listen(addr: &str, device: Option<&cpal::Device>, backend: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let stream = TcpStream::connect(addr)?;
+    let sink = create_sink(backend, device)?;
+    let source: Box<dyn TrackSource> = Box::new(StreamSource { control: stream });
+    play_from_source(sink, source, None, false)
+}
+
+/// Reads the server's framed track announcements one at a time; steps
+/// forward only, since there's no way to rewind a live TCP stream.
+struct StreamSource {
+    control: TcpStream,
+}
+
+impl TrackSource for StreamSource {
+    fn next(&mut self) -> Option<(Track, Box<dyn Source<Item = i16> + Send>)> {
+        let header_frame = read_frame(&mut self.control).ok()?;
+        let (title, sample_rate, channels) = decode_header(&header_frame)?;
+        let audio_stream = self.control.try_clone().ok()?;
+
+        let track = Track { path: title.clone(), title: Some(title), duration: None };
+        let source = NetworkTrackSource {
+            stream: audio_stream,
+            sample_rate,
+            channels,
+            buffer: VecDeque::new(),
+            finished: false,
+        };
+
+        Some((track, Box::new(source)))
+    }
+}
+
+/// Lazily pulls one track's PCM off the wire a chunk at a time as the sink
+/// consumes samples, the same way `Decoder` lazily decodes a file as it's
+/// read, rather than buffering a whole track before playback can start.
+struct NetworkTrackSource {
+    stream: TcpStream,
+    sample_rate: u32,
+    channels: u16,
+    buffer: VecDeque<i16>,
+    finished: bool,
+}
+
+impl Iterator for NetworkTrackSource {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        loop {
+            if let Some(sample) = self.buffer.pop_front() {
+                return Some(sample);
+            }
+            if self.finished {
+                return None;
+            }
+
+            let frame = match read_frame(&mut self.stream) {
+                Ok(frame) => frame,
+                Err(_) => {
+                    self.finished = true;
+                    return None;
+                }
+            };
+            if frame.is_empty() {
+                self.finished = true;
+                return None;
+            }
+            self.buffer.extend(decode_samples(&frame));
+        }
+    }
+}
+
+impl Source for NetworkTrackSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+fn write_frame(stream: &mut TcpStream, payload: &[u8]) -> std::io::Result<()> {
+    stream.write_all(&(payload.len() as u32).to_le_bytes())?;
+    stream.write_all(payload)
+}
+
+fn read_frame(stream: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut len_bytes = [0u8; 4];
+    stream.read_exact(&mut len_bytes)?;
+    let mut payload = vec![0u8; u32::from_le_bytes(len_bytes) as usize];
+    stream.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// `[title_len][title utf8][sample_rate][channels]`
+fn encode_header(title: &str, sample_rate: u32, channels: u16) -> Vec<u8> {
+    let title_bytes = title.as_bytes();
+    let mut buf = Vec::with_capacity(4 + title_bytes.len() + 4 + 2);
+    buf.extend_from_slice(&(title_bytes.len() as u32).to_le_bytes());
+    buf.extend_from_slice(title_bytes);
+    buf.extend_from_slice(&sample_rate.to_le_bytes());
+    buf.extend_from_slice(&channels.to_le_bytes());
+    buf
+}
+
+fn decode_header(buf: &[u8]) -> Option<(String, u32, u16)> {
+    let title_len = u32::from_le_bytes(buf.get(0..4)?.try_into().ok()?) as usize;
+    let title_end = 4 + title_len;
+    let title = String::from_utf8(buf.get(4..title_end)?.to_vec()).ok()?;
+    let sample_rate = u32::from_le_bytes(buf.get(title_end..title_end + 4)?.try_into().ok()?);
+    let channels = u16::from_le_bytes(buf.get(title_end + 4..title_end + 6)?.try_into().ok()?);
+    Some((title, sample_rate, channels))
+}
+
+fn encode_samples(samples: &[i16]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(samples.len() * 2);
+    for sample in samples {
+        buf.extend_from_slice(&sample.to_le_bytes());
+    }
+    buf
+}
+
+fn decode_samples(buf: &[u8]) -> Vec<i16> {
+    buf.chunks_exact(2).map(|c| i16::from_le_bytes([c[0], c[1]])).collect()
+}
@@ -0,0 +1,697 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::time::Duration;
+use std::thread;
+use std::path::Path;
+use std::fs::File;
+use std::io::{self, BufReader};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::{cpal, Decoder, Source};
+use crossterm::{
+    execute,
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    terminal::{disable_raw_mode, enable_raw_mode, Clear, ClearType},
+    cursor::{MoveTo, MoveToNextLine},
+    style::Print,
+};
+
+use crate::backend::{create_sink, AudioSink};
+
+/// How close to the end of the current track (in seconds) we start decoding
+/// the next one, so decode latency never interrupts gapless playback.
+const PRELOAD_THRESHOLD_SECS: u64 = 5;
+
+/// Seek step for a plain arrow key press, and for one held with shift.
+const SEEK_STEP_SECS: u64 = 5;
+const SEEK_STEP_SECS_FAST: u64 = 30;
+
+/// How much a single volume key press changes the gain by, and the ceiling
+/// on how loud the sink will go.
+const VOLUME_STEP: f32 = 0.1;
+const VOLUME_MAX: f32 = 2.0;
+
+/// Requests the render/input loop sends to the player thread.
+pub enum PlayerCommand {
+    TogglePause,
+    Stop,
+    Seek(Duration),
+    SetVolume(f32),
+    Next,
+}
+
+/// Notifications the player thread sends back for the render loop to display.
+pub enum PlayerEvent {
+    Progress { elapsed: Duration, total: Option<Duration> },
+    TrackChanged { index: usize, track: Track },
+    Finished,
+}
+
+/// A queued file and, when it came from a `.m3u`/`.m3u8` playlist's
+/// `#EXTINF` tag, the title to show in place of the raw path and the
+/// reported duration to show before the file has actually been decoded.
+/// A track announced by a network stream has no local `path`; its title
+/// stands in for both.
+#[derive(Clone)]
+pub struct Track {
+    pub path: String,
+    pub title: Option<String>,
+    pub duration: Option<Duration>,
+}
+
+impl Track {
+    pub fn from_path(path: String) -> Self {
+        Self { path, title: None, duration: None }
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.title.as_deref().unwrap_or(&self.path)
+    }
+}
+
+/// Supplies the playback engine with one decoded track at a time. A local
+/// queue (`QueueSource`) knows its whole track list up front; the network
+/// client instead reads whatever the server sends next off the wire and can
+/// only move forward. Both are read in order through `next`, the trait's
+/// only operation.
+pub trait TrackSource: Send {
+    fn next(&mut self) -> Option<(Track, Box<dyn Source<Item = i16> + Send>)>;
+}
+
+/// Feeds the engine from a queue that's already fully known (the local file
+/// browser or a loaded `.m3u8`), decoding each file lazily as it's reached.
+struct QueueSource {
+    tracks: Vec<Track>,
+    next_index: usize,
+}
+
+impl QueueSource {
+    fn new(tracks: Vec<Track>) -> Self {
+        Self { tracks, next_index: 0 }
+    }
+}
+
+impl TrackSource for QueueSource {
+    fn next(&mut self) -> Option<(Track, Box<dyn Source<Item = i16> + Send>)> {
+        let track = self.tracks.get(self.next_index)?.clone();
+        let source = decode_file(&track.path).ok()?;
+        self.next_index += 1;
+        Some((track, source))
+    }
+}
+
+/// Lists the host's output devices and lets the user pick one to play
+/// through. Falls back to the host's default device if there is exactly
+/// one, so a single-device machine skips the prompt entirely.
+pub fn select_output_device() -> Result<cpal::Device, Box<dyn std::error::Error>> {
+    let host = cpal::default_host();
+    let devices: Vec<cpal::Device> = host.output_devices()?.collect();
+
+    if devices.len() <= 1 {
+        return host
+            .default_output_device()
+            .ok_or_else(|| "no audio output device found".into());
+    }
+
+    let names: Vec<String> = devices
+        .iter()
+        .map(|d| d.name().unwrap_or_else(|_| "Unknown device".into()))
+        .collect();
+
+    let selection = dialoguer::Select::new()
+        .with_prompt("Select an output device")
+        .default(0)
+        .items(&names)
+        .interact()?;
+
+    Ok(devices.into_iter().nth(selection).unwrap())
+}
+
+/// Plays `queue` back-to-back with no gap between tracks on `device`
+/// (ignored by every backend but `rodio`), through whichever `AudioSink`
+/// backend `backend` names (see `backend.rs`). Returns once the whole
+/// queue drains or the user quits.
+pub fn play_queue(queue: &[Track], device: Option<&cpal::Device>, backend: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if queue.is_empty() {
+        return Ok(());
+    }
+
+    let sink = create_sink(backend, device)?;
+    let source: Box<dyn TrackSource> = Box::new(QueueSource::new(queue.to_vec()));
+    play_from_source(sink, source, Some(queue.len()), true)
+}
+
+pub fn decode_file(file_path: &str) -> Result<Box<dyn Source<Item = i16> + Send>, Box<dyn std::error::Error>> {
+    let file = File::open(Path::new(file_path))?;
+    Ok(Box::new(Decoder::new_mp3(BufReader::new(file))?))
+}
+
+/// Drives playback from any `TrackSource` until it's exhausted or the user
+/// quits, using the shared crossterm UI. The player thread owns the sink
+/// exclusively; the calling thread only ever talks to it through
+/// `PlayerCommand`s and listens for `PlayerEvent`s to render. `total_tracks`
+/// is `None` when the source doesn't know its own length up front (a
+/// network stream); `can_save_playlist` disables the `'s'` key for sources
+/// that aren't a known list of local files.
+pub fn play_from_source(
+    sink: Box<dyn AudioSink>,
+    source: Box<dyn TrackSource>,
+    total_tracks: Option<usize>,
+    can_save_playlist: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (command_tx, command_rx) = mpsc::channel();
+    let (event_tx, event_rx) = mpsc::channel();
+
+    let engine = PlaybackEngine::new(sink, source, command_rx, event_tx)?;
+    let player_thread = thread::spawn(move || engine.run());
+
+    enable_raw_mode()?;
+    run_render_loop(&command_tx, &event_rx, total_tracks, can_save_playlist)?;
+    cleanup_display()?;
+
+    player_thread.join().unwrap();
+
+    Ok(())
+}
+
+/// Owns the sink and drives playback. Runs on its own thread, draining
+/// `PlayerCommand`s each tick and emitting `PlayerEvent`s for the UI.
+struct PlaybackEngine {
+    sink: Box<dyn AudioSink>,
+    source: Box<dyn TrackSource>,
+    current_index: usize,
+    queued_len: usize,
+    is_paused: bool,
+    current_total: Option<Duration>,
+    /// The next track's metadata and reported total, once preloaded and
+    /// appended to the sink but not yet playing.
+    pending_next: Option<(Track, Option<Duration>)>,
+    commands: Receiver<PlayerCommand>,
+    events: Sender<PlayerEvent>,
+}
+
+impl PlaybackEngine {
+    fn new(
+        mut sink: Box<dyn AudioSink>,
+        mut source: Box<dyn TrackSource>,
+        commands: Receiver<PlayerCommand>,
+        events: Sender<PlayerEvent>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let (first_track, first_source) = source.next().ok_or("track source produced no tracks")?;
+        let current_total = first_source.total_duration().or(first_track.duration);
+        sink.append(first_source);
+        sink.play();
+
+        let _ = events.send(PlayerEvent::TrackChanged { index: 0, track: first_track.clone() });
+
+        Ok(Self {
+            sink,
+            source,
+            current_index: 0,
+            queued_len: 1,
+            is_paused: false,
+            current_total,
+            pending_next: None,
+            commands,
+            events,
+        })
+    }
+
+    /// Drains commands and advances playback until the source is exhausted
+    /// or a `Stop` command is received.
+    fn run(mut self) {
+        loop {
+            match self.drain_commands() {
+                ControlFlow::Stop => {
+                    let _ = self.events.send(PlayerEvent::Finished);
+                    return;
+                }
+                ControlFlow::Continue => {}
+            }
+
+            self.preload_next_track();
+            self.advance_if_track_changed();
+            self.emit_progress();
+
+            if self.sink.empty() && !self.fetch_next_track() {
+                let _ = self.events.send(PlayerEvent::Finished);
+                return;
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    fn drain_commands(&mut self) -> ControlFlow {
+        loop {
+            match self.commands.try_recv() {
+                Ok(command) => {
+                    if let ControlFlow::Stop = self.handle_command(command) {
+                        return ControlFlow::Stop;
+                    }
+                }
+                Err(TryRecvError::Empty) => return ControlFlow::Continue,
+                Err(TryRecvError::Disconnected) => return ControlFlow::Stop,
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: PlayerCommand) -> ControlFlow {
+        match command {
+            PlayerCommand::TogglePause => self.toggle_pause(),
+            PlayerCommand::Stop => {
+                self.sink.stop();
+                return ControlFlow::Stop;
+            }
+            PlayerCommand::Seek(position) => {
+                let position = match self.current_total {
+                    Some(total) => position.min(total),
+                    None => position,
+                };
+                if self.sink.try_seek(position).is_ok() {
+                    self.emit_progress();
+                }
+            }
+            PlayerCommand::SetVolume(volume) => self.sink.set_volume(volume),
+            PlayerCommand::Next => self.skip_to_next(),
+        }
+        ControlFlow::Continue
+    }
+
+    fn toggle_pause(&mut self) {
+        self.is_paused = !self.is_paused;
+        if self.is_paused {
+            self.sink.pause();
+        } else {
+            self.sink.play();
+        }
+    }
+
+    /// Jumps straight to the next track, since a gapless sink can otherwise
+    /// only be advanced by letting the current one finish. If a track was
+    /// already preloaded, it's already appended to the sink and past the
+    /// point `self.source` would give it back out, so skip just the current
+    /// source and let the preloaded one play rather than fetching again
+    /// (which would silently skip it and jump to the one after it).
+    fn skip_to_next(&mut self) {
+        let advanced = match self.pending_next.take() {
+            Some((track, total)) => {
+                self.sink.skip_one();
+                self.current_total = total;
+                self.sink.play();
+                self.current_index += 1;
+                self.queued_len = self.sink.len();
+                self.is_paused = false;
+                let _ = self.events.send(PlayerEvent::TrackChanged { index: self.current_index, track });
+                true
+            }
+            None => {
+                self.sink.clear();
+                self.fetch_next_track()
+            }
+        };
+
+        if !advanced {
+            self.sink.stop();
+        }
+    }
+
+    /// Once the current track is within `PRELOAD_THRESHOLD_SECS` of finishing,
+    /// pull the next track from the source and append it so decode latency
+    /// never interrupts audio. This runs on the engine's own thread, which
+    /// owns the sink exclusively. Skipped for sources (like a network
+    /// stream) that don't report a track length, since there's nothing to
+    /// measure "near the end" against — those fall back to `fetch_next_track`
+    /// once the sink actually runs dry.
+    fn preload_next_track(&mut self) {
+        if self.pending_next.is_some() {
+            return;
+        }
+
+        let near_end = match self.current_total {
+            Some(total) => {
+                let remaining = total.checked_sub(self.elapsed());
+                remaining.map_or(true, |r| r <= Duration::from_secs(PRELOAD_THRESHOLD_SECS))
+            }
+            None => false,
+        };
+
+        if !near_end {
+            return;
+        }
+
+        if let Some((track, source)) = self.source.next() {
+            let total = source.total_duration().or(track.duration);
+            self.sink.append(source);
+            self.queued_len = self.sink.len();
+            self.pending_next = Some((track, total));
+        }
+    }
+
+    /// `Sink::len()` decrements by one each time a queued source finishes and
+    /// the next one starts playing; watch for that to know the active track
+    /// changed without the UI driving it via a command.
+    fn advance_if_track_changed(&mut self) {
+        let remaining = self.sink.len();
+        if remaining >= self.queued_len || remaining == 0 {
+            return;
+        }
+
+        self.queued_len = remaining;
+        self.current_index += 1;
+
+        if let Some((track, total)) = self.pending_next.take() {
+            self.current_total = total;
+            let _ = self.events.send(PlayerEvent::TrackChanged { index: self.current_index, track });
+        }
+    }
+
+    /// Pulls the next track directly from the source and appends it,
+    /// without waiting for a near-end preload; used both for `Next` and as
+    /// the fallback for sources that never got to preload because they
+    /// don't report a track length. Returns `false` once the source is
+    /// exhausted.
+    fn fetch_next_track(&mut self) -> bool {
+        let Some((track, source)) = self.source.next() else {
+            return false;
+        };
+
+        self.current_total = source.total_duration().or(track.duration);
+        self.sink.append(source);
+        self.sink.play();
+
+        self.current_index += 1;
+        self.queued_len = self.sink.len();
+        self.is_paused = false;
+
+        let _ = self.events.send(PlayerEvent::TrackChanged { index: self.current_index, track });
+        true
+    }
+
+    /// The sink's own playback position, rather than wall-clock math, so it
+    /// stays correct across pauses and seeks.
+    fn elapsed(&self) -> Duration {
+        self.sink.get_pos()
+    }
+
+    fn emit_progress(&self) {
+        let _ = self.events.send(PlayerEvent::Progress {
+            elapsed: self.elapsed(),
+            total: self.current_total,
+        });
+    }
+}
+
+enum ControlFlow {
+    Continue,
+    Stop,
+}
+
+/// Reads keyboard input, translates it into `PlayerCommand`s, and renders
+/// whatever `PlayerEvent`s the player thread has emitted since the last tick.
+fn run_render_loop(
+    command_tx: &Sender<PlayerCommand>,
+    event_rx: &Receiver<PlayerEvent>,
+    total_tracks: Option<usize>,
+    can_save_playlist: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut elapsed = Duration::ZERO;
+    let mut total = None;
+    let mut jump_buffer = String::new();
+    let mut status = String::new();
+    let mut volume: f32 = 1.0;
+    let mut played: Vec<Track> = Vec::new();
+
+    loop {
+        if event::poll(Duration::from_millis(100))? {
+            if let Event::Key(key_event) = event::read()? {
+                if key_event.kind == KeyEventKind::Press {
+                    let shift = key_event.modifiers.contains(KeyModifiers::SHIFT);
+                    match key_event.code {
+                        KeyCode::Char(' ') => {
+                            let _ = command_tx.send(PlayerCommand::TogglePause);
+                        }
+                        KeyCode::Char('q') => {
+                            let _ = command_tx.send(PlayerCommand::Stop);
+                        }
+                        KeyCode::Char('n') => {
+                            let _ = command_tx.send(PlayerCommand::Next);
+                        }
+                        KeyCode::Left => {
+                            let step = Duration::from_secs(seek_step_secs(shift));
+                            let target = elapsed.saturating_sub(step);
+                            let _ = command_tx.send(PlayerCommand::Seek(target));
+                        }
+                        KeyCode::Right => {
+                            let step = Duration::from_secs(seek_step_secs(shift));
+                            let target = elapsed + step;
+                            let _ = command_tx.send(PlayerCommand::Seek(target));
+                        }
+                        KeyCode::Char(c) if c.is_ascii_digit() => jump_buffer.push(c),
+                        KeyCode::Backspace => {
+                            jump_buffer.pop();
+                        }
+                        KeyCode::Enter => {
+                            if let (Ok(percent), Some(total)) = (jump_buffer.parse::<u64>(), total) {
+                                let target = total.mul_f64(percent.min(100) as f64 / 100.0);
+                                let _ = command_tx.send(PlayerCommand::Seek(target));
+                            }
+                            jump_buffer.clear();
+                        }
+                        KeyCode::Esc => jump_buffer.clear(),
+                        KeyCode::Up | KeyCode::Char('+') => {
+                            volume = (volume + VOLUME_STEP).min(VOLUME_MAX);
+                            let _ = command_tx.send(PlayerCommand::SetVolume(volume));
+                        }
+                        KeyCode::Down | KeyCode::Char('-') => {
+                            volume = (volume - VOLUME_STEP).max(0.0);
+                            let _ = command_tx.send(PlayerCommand::SetVolume(volume));
+                        }
+                        KeyCode::Char('s') => {
+                            status = if can_save_playlist {
+                                let out = Path::new("session.m3u8");
+                                match crate::playlist::save_playlist(out, &played) {
+                                    Ok(()) => format!("Saved queue to {}", out.display()),
+                                    Err(e) => format!("Failed to save playlist: {}", e),
+                                }
+                            } else {
+                                "Saving a playlist isn't supported for this source".into()
+                            };
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        let mut finished = false;
+        while let Ok(event) = event_rx.try_recv() {
+            match event {
+                PlayerEvent::Progress { elapsed: e, total: t } => {
+                    elapsed = e;
+                    total = t;
+                }
+                PlayerEvent::TrackChanged { index, track } => {
+                    setup_display(track.display_name(), index, total_tracks)?;
+                    played.push(track);
+                    elapsed = Duration::ZERO;
+                }
+                PlayerEvent::Finished => finished = true,
+            }
+        }
+
+        render_progress(elapsed, total, &jump_buffer, &status, volume)?;
+
+        if finished {
+            return Ok(());
+        }
+    }
+}
+
+fn seek_step_secs(shift: bool) -> u64 {
+    if shift { SEEK_STEP_SECS_FAST } else { SEEK_STEP_SECS }
+}
+
+/// Clears the screen and prints the now-playing header; shared by the local
+/// queue UI and the network client, which both show a track name this way.
+/// `total` is `None` when the source doesn't know its own length (a network
+/// stream), in which case the count is rendered as "(N/?)".
+pub fn setup_display(file_path: &str, index: usize, total: Option<usize>) -> Result<(), Box<dyn std::error::Error>> {
+    let total_label = total.map(|t| t.to_string()).unwrap_or_else(|| "?".into());
+    execute!(
+        io::stdout(),
+        Clear(ClearType::All),
+        MoveTo(0, 0),
+        Print(format!("Playing ({}/{}): {}", index + 1, total_label, file_path)),
+        MoveToNextLine(1),
+        Print("SPACE pause/resume, arrows seek (+shift 30s), up/down volume, 'n' next track, digits+ENTER jump %, 's' save playlist, 'q' quit"),
+        MoveToNextLine(1)
+    )?;
+    Ok(())
+}
+
+/// Renders a textual progress bar, e.g. `[####----] 1:23 / 4:10`, plus any
+/// in-progress "jump to percent" digits, the current volume, and the last
+/// status message.
+pub fn render_progress(
+    elapsed: Duration,
+    total: Option<Duration>,
+    jump_buffer: &str,
+    status: &str,
+    volume: f32,
+) -> Result<(), Box<dyn std::error::Error>> {
+    const BAR_WIDTH: usize = 20;
+
+    let ratio = match total {
+        Some(total) if total.as_secs_f64() > 0.0 => {
+            (elapsed.as_secs_f64() / total.as_secs_f64()).clamp(0.0, 1.0)
+        }
+        _ => 0.0,
+    };
+    let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+    let bar = format!("{}{}", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled));
+    let total_label = total.map(format_timestamp).unwrap_or_else(|| "?:??".into());
+
+    execute!(
+        io::stdout(),
+        MoveTo(0, 2),
+        Clear(ClearType::CurrentLine),
+        Print(format!("[{}] {} / {}", bar, format_timestamp(elapsed), total_label)),
+        MoveTo(0, 3),
+        Clear(ClearType::CurrentLine),
+        Print(if jump_buffer.is_empty() {
+            String::new()
+        } else {
+            format!("Jump to: {}%", jump_buffer)
+        }),
+        MoveTo(0, 4),
+        Clear(ClearType::CurrentLine),
+        Print(format!("Volume: {}%", (volume * 100.0).round() as i32)),
+        MoveTo(0, 5),
+        Clear(ClearType::CurrentLine),
+        Print(status)
+    )?;
+    Ok(())
+}
+
+pub fn format_timestamp(d: Duration) -> String {
+    let secs = d.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+pub fn cleanup_display() -> Result<(), Box<dyn std::error::Error>> {
+    disable_raw_mode()?;
+    execute!(
+        io::stdout(),
+        Clear(ClearType::All),
+        MoveTo(0, 0)
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixed-length source with a known, short `total_duration`, so tests
+    /// can drive preload/advance logic without decoding real audio files.
+    struct FixedSource {
+        samples: std::vec::IntoIter<i16>,
+        total: Duration,
+    }
+
+    impl FixedSource {
+        fn new(total: Duration) -> Self {
+            Self { samples: vec![0i16; 4].into_iter(), total }
+        }
+    }
+
+    impl Iterator for FixedSource {
+        type Item = i16;
+
+        fn next(&mut self) -> Option<i16> {
+            self.samples.next()
+        }
+    }
+
+    impl Source for FixedSource {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            1
+        }
+
+        fn sample_rate(&self) -> u32 {
+            1
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            Some(self.total)
+        }
+    }
+
+    /// A `TrackSource` yielding `FixedSource`s with the given totals, one per
+    /// `next()` call, to stand in for a local queue or network stream.
+    struct FixedQueue {
+        totals: std::vec::IntoIter<Duration>,
+        next_index: usize,
+    }
+
+    impl FixedQueue {
+        fn new(totals: Vec<Duration>) -> Self {
+            Self { totals: totals.into_iter(), next_index: 0 }
+        }
+    }
+
+    impl TrackSource for FixedQueue {
+        fn next(&mut self) -> Option<(Track, Box<dyn Source<Item = i16> + Send>)> {
+            let total = self.totals.next()?;
+            let track = Track::from_path(format!("track{}", self.next_index));
+            self.next_index += 1;
+            Some((track, Box::new(FixedSource::new(total))))
+        }
+    }
+
+    fn new_engine(totals: Vec<Duration>) -> (PlaybackEngine, Receiver<PlayerEvent>) {
+        let sink = create_sink("null", None).unwrap();
+        let source: Box<dyn TrackSource> = Box::new(FixedQueue::new(totals));
+        let (_command_tx, command_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+        let engine = PlaybackEngine::new(sink, source, command_rx, event_tx).unwrap();
+        (engine, event_rx)
+    }
+
+    #[test]
+    fn preload_keeps_queued_len_in_sync_with_the_sink() {
+        let (mut engine, _events) = new_engine(vec![Duration::from_millis(20); 3]);
+
+        engine.preload_next_track();
+
+        assert!(engine.pending_next.is_some());
+        assert_eq!(engine.queued_len, engine.sink.len());
+    }
+
+    #[test]
+    fn track_change_is_detected_once_each_preloaded_track_finishes() {
+        let (mut engine, events) = new_engine(vec![Duration::from_millis(20); 3]);
+        engine.sink.play();
+
+        // `PlaybackEngine::new` already announced track 0; drain it so the
+        // assertions below only see the transitions this test drives.
+        assert!(matches!(events.try_recv(), Ok(PlayerEvent::TrackChanged { index: 0, .. })));
+
+        engine.preload_next_track();
+        thread::sleep(Duration::from_millis(40));
+        engine.advance_if_track_changed();
+
+        assert_eq!(engine.current_index, 1);
+        assert!(matches!(events.try_recv(), Ok(PlayerEvent::TrackChanged { index: 1, .. })));
+
+        engine.preload_next_track();
+        thread::sleep(Duration::from_millis(40));
+        engine.advance_if_track_changed();
+
+        assert_eq!(engine.current_index, 2);
+        assert!(matches!(events.try_recv(), Ok(PlayerEvent::TrackChanged { index: 2, .. })));
+    }
+}
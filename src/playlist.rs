@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::path::Path;
+use std::time::Duration;
+
+use m3u8_rs::{MediaPlaylist, MediaSegment, Playlist};
+
+use crate::player::Track;
+
+/// Parses a `.m3u`/`.m3u8` playlist into a queue, resolving each entry
+/// (relative or absolute) against the playlist's own directory and keeping
+/// any `#EXTINF` title and duration for display before the file is decoded.
+pub fn load_playlist(path: &Path) -> Result<Vec<Track>, Box<dyn std::error::Error>> {
+    let bytes = std::fs::read(path)?;
+    let playlist = match m3u8_rs::parse_playlist_res(&bytes) {
+        Ok(Playlist::MediaPlaylist(media)) => media,
+        Ok(Playlist::MasterPlaylist(_)) => return Err("master playlists are not supported for audio queues".into()),
+        Err(_) => return Err(format!("failed to parse playlist: {}", path.display()).into()),
+    };
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    Ok(playlist
+        .segments
+        .into_iter()
+        .map(|segment| Track {
+            path: resolve_playlist_entry(base_dir, &segment.uri),
+            title: segment.title,
+            duration: extinf_duration(segment.duration),
+        })
+        .collect())
+}
+
+/// `m3u8-rs` reports a missing `#EXTINF` duration as `0.0`; treat that as
+/// "unknown" rather than a real zero-length track.
+fn extinf_duration(duration: f32) -> Option<Duration> {
+    if duration > 0.0 {
+        Some(Duration::from_secs_f32(duration))
+    } else {
+        None
+    }
+}
+
+/// Resolves an `.m3u` entry against the playlist's own directory; absolute
+/// entries are left untouched.
+fn resolve_playlist_entry(base_dir: &Path, entry: &str) -> String {
+    let entry_path = Path::new(entry);
+    if entry_path.is_absolute() {
+        entry_path.display().to_string()
+    } else {
+        base_dir.join(entry_path).display().to_string()
+    }
+}
+
+/// Writes the session's track order back out as a new `.m3u8` playlist.
+pub fn save_playlist(path: &Path, queue: &[Track]) -> Result<(), Box<dyn std::error::Error>> {
+    let segments = queue
+        .iter()
+        .map(|track| MediaSegment {
+            uri: track.path.clone(),
+            title: track.title.clone(),
+            duration: track.duration.map(|d| d.as_secs_f32()).unwrap_or(0.0),
+            ..Default::default()
+        })
+        .collect();
+
+    let playlist = MediaPlaylist {
+        version: Some(3),
+        segments,
+        ..Default::default()
+    };
+
+    let mut file = File::create(path)?;
+    playlist.write_to(&mut file)?;
+    Ok(())
+}